@@ -21,6 +21,12 @@ export const CLOUDMUSIC_WEBDB_PATH = path.join(
   "\\Library\\webdb.dat"
 )
 
+// 每首歌最多保留的播放状态切换记录数
+export const CLOUDMUSIC_STATUS_HISTORY_LIMIT = 50
+
+// 最多保留的历史播放歌曲记录数
+export const CLOUDMUSIC_TRACK_HISTORY_LIMIT = 100
+
 export const CLOUDMUSIC_ELOG_MATCHES = {
   EXIT: {
     rule: (row: string) => row.includes(`【app】,{"actionId":"exitApp"}`),
@@ -10,6 +10,8 @@ export default class ElogListener extends Nanobus<{
   private fileSize = 0
   private filePath = CLOUDMUSIC_ELOG_PATH
   private watchOption = { interval: 300 }
+  private watching = false
+  private startId = 0
 
   private _watchListener = (curr: fs.Stats) => {
     if (curr.size < this.fileSize) {
@@ -30,20 +32,46 @@ export default class ElogListener extends Nanobus<{
     this.fileSize = curr.size
   }
 
-  public async start() {
+  public get isWatching() {
+    return this.watching
+  }
+
+  public async start(): Promise<string[] | null> {
+    // 已在监听中，重新读取会与正在进行的增量读取重复触发事件
+    if (this.watching) {
+      return null
+    }
+
+    const startId = ++this.startId
+
     await fsPromises.access(this.filePath)
     const buffer = await fsPromises.readFile(this.filePath)
+
+    // 读取期间调用了 stop 或再次 start，放弃本次启动
+    if (startId !== this.startId) {
+      throw new Error("ElogListener start superseded")
+    }
+
     const dataArray = new Uint8Array(buffer)
     const lines = ElogAnalysis.decode(dataArray).split("\n")
 
     this.fileSize = buffer.length
+
     fs.watchFile(this.filePath, this.watchOption, this._watchListener)
+    this.watching = true
 
     return lines
   }
 
   public stop() {
+    this.startId++
+
+    if (!this.watching) {
+      return
+    }
+
     fs.unwatchFile(this.filePath, this._watchListener)
+    this.watching = false
   }
 
   private emitLine(lines: string[]) {
@@ -2,8 +2,20 @@ import Nanobus from "nanobus"
 import Webdb from "./webdb.js"
 import ElogAnalysis from "./elog-analysis.js"
 import ElogListener from "./elog-listener.js"
-import { CLOUDMUSIC_ELOG_MATCHES } from "./constant.js"
-import type { DetectorStatus, TrackIn } from "./types.js"
+import {
+  CLOUDMUSIC_ELOG_MATCHES,
+  CLOUDMUSIC_STATUS_HISTORY_LIMIT,
+  CLOUDMUSIC_TRACK_HISTORY_LIMIT,
+} from "./constant.js"
+import type {
+  DetectorHistoryEntry,
+  DetectorOptions,
+  DetectorPreloadState,
+  DetectorStatus,
+  DetectorStatusEvent,
+  DetectorTimeUnit,
+  TrackIn,
+} from "./types.js"
 
 export class CloudmusicDetector extends Nanobus<{
   play: (songId: number) => void
@@ -12,6 +24,8 @@ export class CloudmusicDetector extends Nanobus<{
 }> {
   private listener = new ElogListener()
   private webdb = new Webdb()
+  private timeUnit: DetectorTimeUnit
+  private emitInitialSnapshot: boolean
 
   private currentSongId = -1
   private currentSongName = ""
@@ -23,26 +37,63 @@ export class CloudmusicDetector extends Nanobus<{
   private currentSongPosition = 0
   private currentSongPausing = false
   private currentSongRelativeTime = 0
+  private currentSongStatusHistory: DetectorStatusEvent[] = []
 
-  constructor() {
+  private trackHistory: DetectorHistoryEntry[] = []
+
+  // 累计播放时长（秒），playtimeSince 为本段播放开始的时间戳，未播放时为 0
+  private playtimeAccumulated = 0
+  private playtimeSince = 0
+
+  // 每次 start / stop 递增，用于丢弃过期的预加载结果
+  private startId = 0
+
+  constructor(options: DetectorOptions = {}) {
     super("CLOUDMUSIC-DETECTOR")
+    this.timeUnit = options.timeUnit ?? "seconds"
+    this.emitInitialSnapshot = options.emitInitialSnapshot ?? false
     this.bindEvents()
   }
 
-  public start() {
-    return this.listener
-      .start()
-      .then((lines) => this.preloadLines(lines))
-      .catch(() => {})
+  public async start() {
+    const startId = ++this.startId
+
+    try {
+      const lines = await this.listener.start()
+
+      // 已经启动过，保留当前状态
+      if (lines === null) {
+        return
+      }
+
+      const state = await this.preloadLines(lines)
+
+      // 预加载期间已 stop 或重新 start，丢弃本次结果
+      if (startId !== this.startId) {
+        return
+      }
+
+      this.applyPreload(state)
+    } catch {
+      return
+    }
+
+    this.emitSnapshot(startId)
   }
 
   public stop() {
+    this.startId++
     this.listener.stop()
+
+    // 停止监听后无法得知播放状态，结算并暂停计时
+    this.updatePlaytime(Date.now())
+    this.playtimeSince = 0
   }
 
-  private async preloadLines(lines: string[]) {
+  private async preloadLines(lines: string[]): Promise<DetectorPreloadState> {
     const now = Date.now()
     const records: string[] = []
+    const statusEvents: DetectorStatusEvent[] = []
 
     let songId = -1
     let songPlayTime = 0
@@ -61,9 +112,15 @@ export class CloudmusicDetector extends Nanobus<{
 
       switch (ElogAnalysis.getType(line)) {
         case "EXIT": {
-          this.resetState()
-          return
-          // return Promise.resolve()
+          return {
+            now,
+            songId: -1,
+            playTime: headers.timestamp,
+            position: 0,
+            pausing: false,
+            detail: null,
+            statusEvents,
+          }
         }
 
         case "PLAY_ONE_TRACKIN_PLAYING_LIST": {
@@ -164,6 +221,13 @@ export class CloudmusicDetector extends Nanobus<{
             songPosition += offset / 1000
           }
 
+          if (songId !== -1) {
+            statusEvents.push({
+              timestamp: headers.timestamp,
+              playing: !songPausing,
+            })
+          }
+
           break
         }
       }
@@ -173,14 +237,58 @@ export class CloudmusicDetector extends Nanobus<{
       songPosition += (now - lastActionTime) / 1000
     }
 
+    return {
+      now,
+      songId,
+      // 未找到歌曲时以预加载时间作为记录时间
+      playTime: songId === -1 ? now : songPlayTime,
+      position: songPosition,
+      pausing: songPausing,
+      detail: songTrackDetail,
+      statusEvents,
+    }
+  }
+
+  private applyPreload(state: DetectorPreloadState) {
+    const { now, songId, position, pausing, detail } = state
+
+    if (songId === -1) {
+      this.resetState()
+      this.updatePlaytime(now)
+      this.pushTrackHistory(state.playTime)
+      return
+    }
+
     this.currentSongId = songId
-    this.currentSongPausing = songPausing
-    this.currentSongPosition = songPausing ? songPosition : 0
-    this.currentSongRelativeTime = songPausing ? 0 : now - songPosition * 1000
+    this.currentSongPausing = pausing
+    this.currentSongPosition = pausing ? position : 0
+    this.currentSongRelativeTime = pausing ? 0 : now - position * 1000
+    this.updatePlaytime(now)
+
+    // 按日志重建当前歌曲的播放状态切换记录
+    this.currentSongStatusHistory = []
+    state.statusEvents.forEach(({ timestamp, playing }) =>
+      this.pushStatusHistory(timestamp, playing)
+    )
+
+    if (detail) {
+      this.refreshCurrentSongDetail(detail)
+      this.pushTrackHistory(state.playTime)
+    }
+  }
 
-    if (songTrackDetail) {
-      this.refreshCurrentSongDetail(songTrackDetail)
+  private emitSnapshot(startId: number) {
+    if (!this.emitInitialSnapshot || this.currentSongId === -1) {
+      return
     }
+
+    // 只为仍在生效且正在监听的那次 start 触发
+    if (startId !== this.startId || !this.listener.isWatching) {
+      return
+    }
+
+    this.emit("play", this.currentSongId)
+    this.emit("status", !this.currentSongPausing)
   }
 
   private bindEvents() {
@@ -198,6 +306,8 @@ export class CloudmusicDetector extends Nanobus<{
         // 软件退出
         case "EXIT": {
           this.resetState()
+          this.pushTrackHistory(headers.timestamp)
+          this.updatePlaytime(headers.timestamp)
           this.emit("play", this.currentSongId)
           return
         }
@@ -214,6 +324,7 @@ export class CloudmusicDetector extends Nanobus<{
           this.currentSongPausing = true
           this.currentSongPosition = 0
           this.currentSongRelativeTime = 0
+          this.currentSongStatusHistory = []
           this.refreshCurrentSongDetail({
             name: data.name,
             cover: data.album.cover,
@@ -221,6 +332,8 @@ export class CloudmusicDetector extends Nanobus<{
             artists: data.artists.map((item) => item.name),
             duration: data.duration / 1000,
           })
+          this.pushTrackHistory(headers.timestamp)
+          this.updatePlaytime(headers.timestamp)
           this.emit("play", this.currentSongId)
           // this.refreshCurrentSongDetail().then(() => {
           //   this.emit("play", this.currentSongId)
@@ -241,6 +354,7 @@ export class CloudmusicDetector extends Nanobus<{
           this.currentSongPausing = false
           this.currentSongPosition = 0
           this.currentSongRelativeTime = 0
+          this.currentSongStatusHistory = []
           this.refreshCurrentSongDetail({
             name: data.track.name,
             cover: data.track.album.cover,
@@ -248,6 +362,8 @@ export class CloudmusicDetector extends Nanobus<{
             artists: data.track.artists.map((item) => item.name),
             duration: data.track.duration / 1000,
           })
+          this.pushTrackHistory(headers.timestamp)
+          this.updatePlaytime(headers.timestamp)
           this.emit("play", this.currentSongId)
 
           break
@@ -272,6 +388,7 @@ export class CloudmusicDetector extends Nanobus<{
           this.currentSongRelativeTime = this.currentSongPausing
             ? 0
             : newRelative - offset * 1000
+          this.currentSongStatusHistory = []
 
           this.refreshCurrentSongDetail({
             name: track.name,
@@ -280,6 +397,8 @@ export class CloudmusicDetector extends Nanobus<{
             artists: track.artists.map((item) => item.name),
             duration: track.duration / 1000,
           })
+          this.pushTrackHistory(headers.timestamp)
+          this.updatePlaytime(headers.timestamp)
           this.emit("play", this.currentSongId)
         }
 
@@ -321,6 +440,10 @@ export class CloudmusicDetector extends Nanobus<{
             ? 0
             : newRelative - offset * 1000
 
+          if (this.currentSongId !== -1) {
+            this.pushStatusHistory(headers.timestamp, !this.currentSongPausing)
+          }
+          this.updatePlaytime(headers.timestamp)
           this.emit("status", !this.currentSongPausing)
           break
         }
@@ -333,6 +456,111 @@ export class CloudmusicDetector extends Nanobus<{
     this.currentSongPosition = 0
     this.currentSongPausing = false
     this.currentSongRelativeTime = 0
+    this.currentSongStatusHistory = []
+  }
+
+  private pushStatusHistory(timestamp: number, playing: boolean) {
+    const history = this.currentSongStatusHistory
+    const last = history[history.length - 1]
+
+    // 只记录状态切换，忽略重复的同一状态
+    if (last && last.playing === playing) {
+      return
+    }
+
+    history.push({ timestamp, playing })
+
+    if (history.length > CLOUDMUSIC_STATUS_HISTORY_LIMIT) {
+      history.shift()
+    }
+  }
+
+  public getStatusHistory(): DetectorStatusEvent[] {
+    return this.currentSongStatusHistory.map((event) => ({ ...event }))
+  }
+
+  private pushTrackHistory(timestamp: number) {
+    const history = this.trackHistory
+    const last = history[history.length - 1]
+
+    // 重复 start 时预加载会再次记录同一首歌
+    if (last && last.id === this.currentSongId) {
+      return
+    }
+
+    const available = this.currentSongId !== -1
+
+    history.push({
+      timestamp,
+      id: this.currentSongId,
+      duration: available ? this.currentSongDuration : 0,
+      detail: available
+        ? {
+            name: this.currentSongName,
+            cover: this.currentSongCover,
+            albumName: this.currentSongAlbumName,
+            artistNames: this.currentSongArtistNames,
+          }
+        : null,
+    })
+
+    if (history.length > CLOUDMUSIC_TRACK_HISTORY_LIMIT) {
+      history.shift()
+    }
+  }
+
+  // 获取指定时间点（毫秒时间戳）正在播放的歌曲
+  public getHistoryAt(epochMs: number): DetectorHistoryEntry | null {
+    for (let i = this.trackHistory.length - 1; i >= 0; i--) {
+      const entry = this.trackHistory[i]
+
+      if (entry.timestamp > epochMs) {
+        continue
+      }
+
+      if (entry.id === -1) {
+        return null
+      }
+
+      return {
+        ...entry,
+        duration: this.toTimeUnit(entry.duration),
+        detail: entry.detail && {
+          ...entry.detail,
+          artistNames: [...entry.detail.artistNames],
+        },
+      }
+    }
+
+    return null
+  }
+
+  // 结算上一段播放时长，并按当前状态决定是否开始新的一段
+  private updatePlaytime(timestamp: number) {
+    if (this.playtimeSince > 0) {
+      this.playtimeAccumulated +=
+        Math.max(timestamp - this.playtimeSince, 0) / 1000
+    }
+
+    this.playtimeSince =
+      this.currentSongId !== -1 && !this.currentSongPausing ? timestamp : 0
+  }
+
+  public getAccumulatedPlaytime() {
+    const playing =
+      this.playtimeSince > 0
+        ? Math.max(Date.now() - this.playtimeSince, 0) / 1000
+        : 0
+
+    return this.toTimeUnit(this.playtimeAccumulated + playing)
+  }
+
+  public resetPlaytime() {
+    this.playtimeAccumulated = 0
+
+    if (this.playtimeSince > 0) {
+      this.playtimeSince = Date.now()
+    }
   }
 
   private async refreshCurrentSongDetail({
@@ -349,34 +577,51 @@ export class CloudmusicDetector extends Nanobus<{
     this.currentSongDuration = duration
   }
 
+  private toTimeUnit(seconds: number) {
+    return this.timeUnit === "milliseconds"
+      ? Math.round(seconds * 1000)
+      : seconds
+  }
+
   public get status(): DetectorStatus {
     const now = Date.now()
 
-    return this.currentSongId === -1
-      ? {
-          available: false,
-          id: -1,
-          playing: false,
-          position: 0,
-          duration: 0,
-        }
-      : {
-          available: true,
-          id: this.currentSongId,
-          playing: !this.currentSongPausing,
-          position: Math.min(
-            this.currentSongPausing
-              ? this.currentSongPosition
-              : (now - this.currentSongRelativeTime) / 1000,
-            this.currentSongDuration
-          ),
-          duration: this.currentSongDuration,
-          detail: {
-            name: this.currentSongName,
-            cover: this.currentSongCover,
-            albumName: this.currentSongAlbumName,
-            artistNames: this.currentSongArtistNames,
-          },
-        }
+    if (this.currentSongId === -1) {
+      return {
+        available: false,
+        id: -1,
+        playing: false,
+        position: 0,
+        duration: 0,
+        progress: 0,
+      }
+    }
+
+    const position = Math.min(
+      this.currentSongPausing
+        ? this.currentSongPosition
+        : (now - this.currentSongRelativeTime) / 1000,
+      this.currentSongDuration
+    )
+
+    const progress =
+      this.currentSongDuration > 0
+        ? Math.min(Math.max(position / this.currentSongDuration, 0), 1)
+        : 0
+
+    return {
+      available: true,
+      id: this.currentSongId,
+      playing: !this.currentSongPausing,
+      position: this.toTimeUnit(position),
+      duration: this.toTimeUnit(this.currentSongDuration),
+      progress,
+      detail: {
+        name: this.currentSongName,
+        cover: this.currentSongCover,
+        albumName: this.currentSongAlbumName,
+        artistNames: this.currentSongArtistNames,
+      },
+    }
   }
 }
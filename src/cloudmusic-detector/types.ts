@@ -200,12 +200,16 @@ export type DetectorAvailableStatus = {
   playing: boolean
   position: number
   duration: number
-  detail?: {
-    name: string
-    cover: string
-    albumName: string
-    artistNames: string[]
-  }
+  // 播放进度，范围 0 ~ 1
+  progress: number
+  detail?: DetectorTrackDetail
+}
+
+export type DetectorTrackDetail = {
+  name: string
+  cover: string
+  albumName: string
+  artistNames: string[]
 }
 
 export type TrackIn = {
@@ -249,6 +253,39 @@ export type DetectorUnavailableStatus = {
   playing: false
   position: 0
   duration: number
+  progress: 0
 }
 
 export type DetectorStatus = DetectorAvailableStatus | DetectorUnavailableStatus
+
+export type DetectorStatusEvent = {
+  timestamp: number
+  playing: boolean
+}
+
+export type DetectorHistoryEntry = {
+  timestamp: number
+  id: number
+  duration: number
+  detail: DetectorTrackDetail | null
+}
+
+// 启动时从 elog 中恢复出的播放状态
+export type DetectorPreloadState = {
+  now: number
+  songId: number
+  playTime: number
+  position: number
+  pausing: boolean
+  detail: TrackIn | null
+  statusEvents: DetectorStatusEvent[]
+}
+
+export type DetectorTimeUnit = "seconds" | "milliseconds"
+
+export type DetectorOptions = {
+  // position / duration 的单位，默认为秒
+  timeUnit?: DetectorTimeUnit
+  // start 完成后是否立即触发一次当前歌曲的 play / status 事件
+  emitInitialSnapshot?: boolean
+}
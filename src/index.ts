@@ -12,8 +12,7 @@ const logger = () => {
       2,
       "0"
     )}:${`${Math.round(detector.status.position % 60)}`.padStart(2, "0")}`,
-    `${Math.round((detector.status.position / detector.status.duration) * 10000) /
-      100}%`
+    `${Math.round(detector.status.progress * 10000) / 100}%`
   )
 }
 